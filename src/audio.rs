@@ -0,0 +1,74 @@
+use std::f32::consts::PI;
+use std::time::Duration;
+
+use rodio::{OutputStream, Sink, Source};
+
+const SAMPLE_RATE: u32 = 44100;
+const BEEP_FREQUENCY: f32 = 440.0;
+
+/// Infinite square-wave source used for the CHIP-8 buzzer.
+struct SquareWave {
+    num_sample: usize,
+}
+
+impl SquareWave {
+    fn new() -> Self {
+        Self { num_sample: 0 }
+    }
+}
+
+impl Iterator for SquareWave {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.num_sample = self.num_sample.wrapping_add(1);
+        let phase = self.num_sample as f32 * BEEP_FREQUENCY * 2.0 * PI / SAMPLE_RATE as f32;
+        Some(if phase.sin() >= 0.0 { 0.2 } else { -0.2 })
+    }
+}
+
+impl Source for SquareWave {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Plays a beep whenever the CHIP-8 sound timer is active, and silence otherwise.
+pub struct Beeper {
+    _stream: OutputStream,
+    sink: Sink,
+}
+
+impl Beeper {
+    /// Opens the default audio output device. Returns `None` if no device is available,
+    /// in which case the emulator should simply run without sound.
+    pub fn new() -> Option<Self> {
+        let (stream, handle) = OutputStream::try_default().ok()?;
+        let sink = Sink::try_new(&handle).ok()?;
+        sink.append(SquareWave::new());
+        sink.pause();
+
+        Some(Self { _stream: stream, sink })
+    }
+
+    /// Starts or stops the beep. Cheap to call every frame; only toggles on edges.
+    pub fn set_playing(&self, playing: bool) {
+        if playing {
+            self.sink.play();
+        } else {
+            self.sink.pause();
+        }
+    }
+}
@@ -11,6 +11,89 @@ const CARRY_REG: usize = 0xF;
 
 const PROGRAM_OFFSET: usize = 0x200;
 
+/// Configuration for opcode behaviors that diverged between the original CHIP-8
+/// interpreter and later SUPER-CHIP implementations. ROMs are typically authored
+/// against one or the other, so the caller picks the set that matches the ROM.
+#[derive(Clone, Copy)]
+pub struct Quirks {
+    /// 8xy6/8xyE: shift Vx in place (true) or shift Vy into Vx first (false, original CHIP-8).
+    pub shift_vx_in_place: bool,
+    /// Bnnn/Bxnn: jump to nnn + Vx, using the opcode's second nibble as x (true, SUPER-CHIP)
+    /// instead of always adding V0 (false, original CHIP-8).
+    pub jump_vx_offset: bool,
+    /// Fx55/Fx65: increment `index` past the last register touched by the load/store loop.
+    pub increment_index_on_load_store: bool,
+    /// Dxyn: clip sprites at the screen edge (true, SUPER-CHIP) instead of wrapping (false).
+    pub clip_sprites: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift_vx_in_place: true,
+            jump_vx_offset: false,
+            increment_index_on_load_store: true,
+            clip_sprites: false,
+        }
+    }
+}
+
+/// Errors that can occur while loading a ROM into memory.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The ROM doesn't fit in the space available after the program offset.
+    TooLarge { size: usize, max: usize },
+    /// The ROM could not be read from disk.
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::TooLarge { size, max } => write!(f, "ROM is {} bytes, but only {} bytes are available", size, max),
+            LoadError::Io(e) => write!(f, "unable to read ROM: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<std::io::Error> for LoadError {
+    fn from(e: std::io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"C8SS";
+const SNAPSHOT_VERSION: u8 = 1;
+
+/// Errors that can occur while restoring a snapshot produced by [`Chip8::snapshot`].
+#[derive(Debug)]
+pub enum SnapshotError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::BadMagic => write!(f, "not a chip-8 snapshot"),
+            SnapshotError::UnsupportedVersion(v) => write!(f, "unsupported snapshot version {}", v),
+            SnapshotError::Truncated => write!(f, "snapshot data is truncated"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// Reads `len` bytes from `data` starting at `*cursor`, advancing `*cursor` past them.
+fn take<'a>(data: &'a [u8], cursor: &mut usize, len: usize) -> Result<&'a [u8], SnapshotError> {
+    let slice = data.get(*cursor..*cursor + len).ok_or(SnapshotError::Truncated)?;
+    *cursor += len;
+    Ok(slice)
+}
+
 const FONT_OFFSET: usize = 0x50;
 const FONT_SIZE: usize = 5;
 const FONT_COUNT: usize = 16;
@@ -43,11 +126,13 @@ pub struct Chip8 {
     delay_timer: Byte,
     sound_timer: Byte,
     op: Word,
-    pub key: u8,
+    pub keys: [bool; 16],
+    prev_keys: [bool; 16],
+    quirks: Quirks,
 }
 
 impl Chip8 {
-    pub fn new() -> Self {
+    pub fn new(quirks: Quirks) -> Self {
         let mut chip8 = Self {
             display: [[false; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize],
             memory: [0; MEM_COUNT],
@@ -58,7 +143,9 @@ impl Chip8 {
             delay_timer: 0,
             sound_timer: 0,
             op: 0,
-            key: 0,
+            keys: [false; 16],
+            prev_keys: [false; 16],
+            quirks,
         };
 
         chip8.load_fonts();
@@ -66,17 +153,29 @@ impl Chip8 {
         chip8
     }
 
-    pub fn load_rom(&mut self, rom: &str) -> Result<(), std::io::Error> {
-        let file = std::fs::File::open(rom)
-            .expect("Unable to read ROM");
+    /// Loads a ROM from an in-memory byte slice, failing if it doesn't fit in the
+    /// space available after the program offset.
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<usize, LoadError> {
+        let max = MEM_COUNT - PROGRAM_OFFSET;
+        if rom.len() > max {
+            return Err(LoadError::TooLarge { size: rom.len(), max });
+        }
+
+        self.memory[PROGRAM_OFFSET..PROGRAM_OFFSET + rom.len()].copy_from_slice(rom);
+
+        Ok(rom.len())
+    }
+
+    pub fn load_rom(&mut self, rom: &str) -> Result<usize, LoadError> {
+        let mut file = std::fs::File::open(rom)?;
 
-        let mut reader = std::io::BufReader::new(file);
-        let loaded = std::io::Read::read(&mut reader, &mut self.memory[PROGRAM_OFFSET..])
-            .expect("Unable to load ROM");
+        let mut bytes = Vec::new();
+        std::io::Read::read_to_end(&mut file, &mut bytes)?;
 
+        let loaded = self.load_rom_bytes(&bytes)?;
         println!("Loaded {} bytes", loaded);
 
-        Ok(())
+        Ok(loaded)
     }
 
     fn load_fonts(&mut self) {
@@ -85,7 +184,24 @@ impl Chip8 {
         }
     }
 
-    fn decrement_timers(&mut self) {
+    /// Snapshots the current key state so the next frame's `handle_op` cycles can detect
+    /// release-to-press edges for `Fx0A`. Call this once per frame, after running that
+    /// frame's instruction cycles (not before, or `prev_keys` would already equal `keys`
+    /// for the whole frame and `Fx0A` would never see an edge; not once per cycle, or a
+    /// key pressed mid-frame would have its edge consumed by whichever unrelated
+    /// instruction happens to run first).
+    pub fn sync_key_edges(&mut self) {
+        self.prev_keys = self.keys;
+    }
+
+    /// Whether the sound timer is currently active, i.e. the buzzer should be sounding.
+    pub fn is_sound_playing(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// Decrements `delay_timer` and `sound_timer`. Meant to be called once per 1/60 s,
+    /// independently of how many instruction cycles run per frame.
+    pub fn tick_timers(&mut self) {
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -95,8 +211,6 @@ impl Chip8 {
     }
 
     pub fn handle_op(&mut self) {
-        self.decrement_timers();
-
         self.op = (self.memory[self.program_counter as usize] as Word) << 8
             | self.memory[(self.program_counter + 1) as usize] as Word;
 
@@ -121,7 +235,7 @@ impl Chip8 {
             0xC => self.rand(),
             0xD => self.draw(),
             0xE => self.keyboard_op(),
-            0xF => self.misc_op(),
+            0xF => if self.misc_op() { jump = true },
             _ => eprintln!("Unhandled opcode: {:#X}", self.op)
         }
 
@@ -143,7 +257,8 @@ impl Chip8 {
     }
 
     fn jump_addr_offset(&mut self) {
-        self.program_counter = (self.op & 0x0FFF) + self.registers[0] as Word;
+        let reg = if self.quirks.jump_vx_offset { ((self.op & 0x0F00) >> 8) as usize } else { 0 };
+        self.program_counter = (self.op & 0x0FFF) + self.registers[reg] as Word;
     }
 
     fn call_addr(&mut self) {
@@ -266,8 +381,10 @@ impl Chip8 {
             // Set Vx = Vx SHR 1.
             // If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0. Then Vx is divided by 2.
             0x6 => {
-                self.registers[CARRY_REG] = self.registers[x] & 0x1;
-                self.registers[x] >>= 1;
+                // With `shift_vx_in_place` off, Vy's bit is tested and Vy is shifted into Vx instead.
+                let shifted = if self.quirks.shift_vx_in_place { self.registers[x] } else { self.registers[y] };
+                self.registers[CARRY_REG] = shifted & 0x1;
+                self.registers[x] = shifted >> 1;
             },
 
             // 8xy7 - SUBN Vx, Vy
@@ -282,8 +399,10 @@ impl Chip8 {
             // Set Vx = Vx SHL 1.
             // If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx is multiplied by 2.
             0xE => {
-                self.registers[CARRY_REG] = (self.registers[x] & 0x80) >> 7;
-                self.registers[x] <<= 1;
+                // With `shift_vx_in_place` off, Vy's bit is tested and Vy is shifted into Vx instead.
+                let shifted = if self.quirks.shift_vx_in_place { self.registers[x] } else { self.registers[y] };
+                self.registers[CARRY_REG] = (shifted & 0x80) >> 7;
+                self.registers[x] = shifted << 1;
             },
 
             _ => eprintln!("Unknown logical op: {:#X}", self.op),
@@ -309,25 +428,30 @@ impl Chip8 {
     }
 
     fn draw(&mut self) {
-        let x = self.registers[((self.op & 0x0F00) >> 8) as usize] as u8;
-        let y = self.registers[((self.op & 0x00F0) >> 4) as usize] as u8;
-        let h = (self.op & 0x000F) as u8;
+        let x = self.registers[((self.op & 0x0F00) >> 8) as usize] as u16 % SCREEN_WIDTH as u16;
+        let y = self.registers[((self.op & 0x00F0) >> 4) as usize] as u16 % SCREEN_HEIGHT as u16;
+        let h = (self.op & 0x000F) as u16;
 
         self.registers[0xF] = 0;
 
         // self.display = [[false; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize];
 
         for h in 0..h {
-            let line = self.memory[(self.index + h as u16) as usize];
-            for w in 0..8 {
+            let line = self.memory[(self.index + h) as usize];
+            for w in 0..8u16 {
                 let pixel = (line >> (7 - w)) & 0x1;
                 if pixel == 1 {
-                    let x = (x + w) % (SCREEN_WIDTH as u8);
-                    let y = (y + h) % (SCREEN_HEIGHT as u8);
-                    if self.display[y as usize][x as usize] {
+                    let raw_x = x + w;
+                    let raw_y = y + h;
+                    if self.quirks.clip_sprites && (raw_x >= SCREEN_WIDTH as u16 || raw_y >= SCREEN_HEIGHT as u16) {
+                        continue;
+                    }
+                    let x = (raw_x % SCREEN_WIDTH as u16) as usize;
+                    let y = (raw_y % SCREEN_HEIGHT as u16) as usize;
+                    if self.display[y][x] {
                         self.registers[0xF] = 1;
                     }
-                    self.display[y as usize][x as usize] ^= true;
+                    self.display[y][x] ^= true;
                 }
             }
         }
@@ -343,7 +467,7 @@ impl Chip8 {
             // Skip next instruction if key with the value of Vx is pressed.
             // Checks the keyboard, and if the key corresponding to the value of Vx is currently in the down position, PC is increased by 2.
             0x9E => {
-                if self.key == self.registers[reg] {
+                if self.keys[(self.registers[reg] & 0x0F) as usize] {
                     self.program_counter += 2;
                 }
             },
@@ -352,7 +476,7 @@ impl Chip8 {
             // Skip next instruction if key with the value of Vx is not pressed.
             // Checks the keyboard, and if the key corresponding to the value of Vx is currently in the up position, PC is increased by 2.
             0xA1 => {
-                if self.key != self.registers[reg] {
+                if !self.keys[(self.registers[reg] & 0x0F) as usize] {
                     self.program_counter += 2;
                 }
             },
@@ -361,12 +485,21 @@ impl Chip8 {
         }
     }
 
-    fn misc_op(&mut self) {
+    /// Fx0A - LD Vx, K
+    /// Wait for a key press, store the value of the key in Vx.
+    /// All execution stops until a key is pressed, then the value of that key is stored in Vx.
+    /// Returns `true` while still waiting, so `handle_op` can leave the program counter untouched.
+    fn misc_op(&mut self) -> bool {
         println!("Misc op: {:#X}", self.op);
         let reg = ((self.op & 0x0F00) >> 8) as usize;
         match self.op & 0x00FF {
             0x07 => self.registers[reg] = self.delay_timer,
-            0x0A => self.registers[reg] = self.key,
+            0x0A => {
+                match (0..self.keys.len()).find(|&k| self.keys[k] && !self.prev_keys[k]) {
+                    Some(k) => self.registers[reg] = k as Byte,
+                    None => return true,
+                }
+            },
             0x15 => self.delay_timer = self.registers[reg],
             0x18 => self.sound_timer = self.registers[reg],
             0x1E => self.index += self.registers[reg] as u16,
@@ -380,15 +513,96 @@ impl Chip8 {
                 for i in 0..=reg {
                     self.memory[self.index as usize + i] = self.registers[i];
                 }
-                self.index = self.index + reg as u16 + 1;
+                if self.quirks.increment_index_on_load_store {
+                    self.index = self.index + reg as u16 + 1;
+                }
             },
             0x65 => {
                 for i in 0..=reg {
                     self.registers[i] = self.memory[self.index as usize + i];
                 }
-                self.index = self.index + reg as u16 + 1;
+                if self.quirks.increment_index_on_load_store {
+                    self.index = self.index + reg as u16 + 1;
+                }
             },
             _ => eprintln!("Unhandled misc opcode: {:#X}", self.op)
         }
+
+        false
+    }
+
+    /// Encodes the full machine state into a compact binary blob, prefixed with a
+    /// magic/version header for forward compatibility.
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+
+        buf.extend_from_slice(SNAPSHOT_MAGIC);
+        buf.push(SNAPSHOT_VERSION);
+
+        buf.extend_from_slice(&self.memory);
+        buf.extend_from_slice(&self.registers);
+        buf.extend_from_slice(&self.index.to_le_bytes());
+        buf.extend_from_slice(&self.program_counter.to_le_bytes());
+
+        buf.push(self.stack.len() as u8);
+        for i in 0..STACK_COUNT {
+            let word = self.stack.get(i).copied().unwrap_or(0);
+            buf.extend_from_slice(&word.to_le_bytes());
+        }
+
+        buf.push(self.delay_timer);
+        buf.push(self.sound_timer);
+
+        for row in &self.display {
+            buf.extend(row.iter().map(|&pixel| pixel as u8));
+        }
+
+        buf.extend(self.keys.iter().map(|&key| key as u8));
+
+        buf
+    }
+
+    /// Restores the full machine state from a blob produced by [`Chip8::snapshot`].
+    pub fn restore(&mut self, data: &[u8]) -> Result<(), SnapshotError> {
+        let cursor = &mut 0usize;
+
+        if take(data, cursor, SNAPSHOT_MAGIC.len())? != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+
+        let version = take(data, cursor, 1)?[0];
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        self.memory.copy_from_slice(take(data, cursor, MEM_COUNT)?);
+        self.registers.copy_from_slice(take(data, cursor, REG_COUNT)?);
+        self.index = Word::from_le_bytes(take(data, cursor, 2)?.try_into().unwrap());
+        self.program_counter = Word::from_le_bytes(take(data, cursor, 2)?.try_into().unwrap());
+
+        let stack_len = take(data, cursor, 1)?[0] as usize;
+        self.stack.clear();
+        for i in 0..STACK_COUNT {
+            let word = Word::from_le_bytes(take(data, cursor, 2)?.try_into().unwrap());
+            if i < stack_len {
+                self.stack.push(word);
+            }
+        }
+
+        self.delay_timer = take(data, cursor, 1)?[0];
+        self.sound_timer = take(data, cursor, 1)?[0];
+
+        for row in self.display.iter_mut() {
+            for pixel in row.iter_mut() {
+                *pixel = take(data, cursor, 1)?[0] != 0;
+            }
+        }
+
+        for key in self.keys.iter_mut() {
+            *key = take(data, cursor, 1)?[0] != 0;
+        }
+        self.prev_keys = self.keys;
+
+        Ok(())
     }
 }
@@ -1,5 +1,6 @@
 
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 use pixels::{Pixels, SurfaceTexture, wgpu::Color};
 use winit::event::ElementState;
@@ -10,9 +11,11 @@ use winit::{
     window::WindowBuilder,
 };
 
-use clap::Parser;
+use clap::{ArgAction, Parser};
 
+mod audio;
 mod chip8;
+use audio::Beeper;
 use chip8::*;
 
 #[derive(Parser)]
@@ -20,15 +23,50 @@ use chip8::*;
 struct Cli {
     #[arg(short, long, value_name="FILE", help="ROM file")]
     rom: String,
+
+    /// Instruction cycles executed per 60 Hz frame (roughly cycles_per_frame * 60 Hz effective clock speed)
+    #[arg(long, default_value_t = 10)]
+    cycles_per_frame: u32,
+
+    /// Disable the buzzer
+    #[arg(long, default_value_t = false)]
+    mute: bool,
+
+    /// 8xy6/8xyE: shift Vx in place. Pass `--shift-vx-in-place=false` for the original
+    /// CHIP-8 behavior of shifting Vy into Vx.
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    shift_vx_in_place: bool,
+
+    /// Bxnn: add Vx (x being the opcode's second nibble) instead of V0 when jumping with offset (SUPER-CHIP behavior).
+    #[arg(long, default_value_t = false)]
+    jump_vx_offset: bool,
+
+    /// Fx55/Fx65: increment I past the last register touched by the load/store loop.
+    /// Pass `--increment-index-on-load-store=false` to leave I unchanged.
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    increment_index_on_load_store: bool,
+
+    /// Dxyn: clip sprites at the screen edge instead of wrapping (SUPER-CHIP behavior).
+    #[arg(long, default_value_t = false)]
+    clip_sprites: bool,
 }
 
+const TIMER_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
 fn main() {
     let cli = Cli::parse();
     let rom = cli.rom;
 
-    let mut chip8 = Chip8::new();
+    let quirks = Quirks {
+        shift_vx_in_place: cli.shift_vx_in_place,
+        jump_vx_offset: cli.jump_vx_offset,
+        increment_index_on_load_store: cli.increment_index_on_load_store,
+        clip_sprites: cli.clip_sprites,
+    };
+
+    let mut chip8 = Chip8::new(quirks);
     chip8.load_rom(rom.as_str())
-        .expect("TODO: panic message");
+        .expect("Failed to load ROM");
 
     // CHIP-8 key mapping
     // |1|2|3|C| => |1|2|3|4|
@@ -78,6 +116,14 @@ fn main() {
 
     pixels.set_clear_color(Color::BLACK);
 
+    let beeper = if cli.mute { None } else { Beeper::new() };
+    let mut sound_was_playing = false;
+
+    let mut saved_snapshot: Option<Vec<u8>> = None;
+
+    let mut last_tick = Instant::now();
+    let mut timer_accumulator = Duration::ZERO;
+
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
 
@@ -87,11 +133,21 @@ fn main() {
 
                 WindowEvent::KeyboardInput { input, .. } => {
                     if let Some(key) = input.virtual_keycode {
-                        if let Some(chip8_key) = key_mapping.get(&key) {
-                            if input.state == ElementState::Released {
-                                chip8.key = 0;
-                            } else {
-                                chip8.key = *chip8_key;
+                        if let Some(&chip8_key) = key_mapping.get(&key) {
+                            chip8.keys[chip8_key as usize] = input.state == ElementState::Pressed;
+                        }
+
+                        if input.state == ElementState::Pressed {
+                            match key {
+                                VirtualKeyCode::F5 => saved_snapshot = Some(chip8.snapshot()),
+                                VirtualKeyCode::F9 => {
+                                    if let Some(snapshot) = &saved_snapshot {
+                                        if let Err(e) = chip8.restore(snapshot) {
+                                            eprintln!("Failed to restore snapshot: {}", e);
+                                        }
+                                    }
+                                },
+                                _ => (),
                             }
                         }
                     }
@@ -100,7 +156,27 @@ fn main() {
                 _ => (),
             },
 
-            Event::MainEventsCleared => {                
+            Event::MainEventsCleared => {
+                let now = Instant::now();
+                timer_accumulator += now - last_tick;
+                last_tick = now;
+                while timer_accumulator >= TIMER_STEP {
+                    for _ in 0..cli.cycles_per_frame {
+                        chip8.handle_op();
+                    }
+                    chip8.sync_key_edges();
+                    chip8.tick_timers();
+                    timer_accumulator -= TIMER_STEP;
+                }
+
+                let sound_playing = chip8.is_sound_playing();
+                if sound_playing != sound_was_playing {
+                    if let Some(beeper) = &beeper {
+                        beeper.set_playing(sound_playing);
+                    }
+                    sound_was_playing = sound_playing;
+                }
+
                 let frame = pixels.get_frame_mut();
                 for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
                     let x = i % SCREEN_WIDTH as usize;
@@ -116,8 +192,6 @@ fn main() {
                     *control_flow = ControlFlow::Exit;
                     return;
                 }
-
-                chip8.handle_op();
             },
 
             _ => (),